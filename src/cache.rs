@@ -0,0 +1,354 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "compress")]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{ScandirResult, ScandirResults};
+
+const MAGIC: &[u8; 4] = b"SCDR";
+const VERSION: u8 = 1;
+/// A length prefix of `u32::MAX` can never occur for a real entry (that
+/// would be a 4 GiB single serialized entry), so it doubles as the
+/// end-of-stream marker.
+const END_MARKER: u32 = u32::MAX;
+
+/// On-disk serialization format recorded in a cache file's header, so a
+/// reader opened against a build with a different feature set is rejected
+/// with a clear [`Error`] instead of garbage output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFormat {
+    #[cfg(feature = "speedy")]
+    Speedy,
+    #[cfg(feature = "bincode")]
+    Bincode,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl CacheFormat {
+    fn tag(self) -> u8 {
+        match self {
+            #[cfg(feature = "speedy")]
+            CacheFormat::Speedy => 1,
+            #[cfg(feature = "bincode")]
+            CacheFormat::Bincode => 2,
+            #[cfg(feature = "json")]
+            CacheFormat::Json => 3,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self, Error> {
+        match tag {
+            #[cfg(feature = "speedy")]
+            1 => Ok(CacheFormat::Speedy),
+            #[cfg(feature = "bincode")]
+            2 => Ok(CacheFormat::Bincode),
+            #[cfg(feature = "json")]
+            3 => Ok(CacheFormat::Json),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("cache file uses format tag {tag}, which this build doesn't support"),
+            )),
+        }
+    }
+}
+
+/// Minimal table-based CRC-32 (IEEE 802.3), accumulated incrementally so the
+/// whole entry stream never needs to be buffered just to checksum it.
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Crc32(!0)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let index = ((self.0 ^ byte as u32) & 0xff) as usize;
+            self.0 = CRC32_TABLE[index] ^ (self.0 >> 8);
+        }
+    }
+
+    fn finalize(self) -> u32 {
+        !self.0
+    }
+}
+
+static CRC32_TABLE: [u32; 256] = {
+    const POLY: u32 = 0xedb88320;
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 { POLY ^ (crc >> 1) } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn encode_result(_result: &ScandirResult, format: CacheFormat) -> Result<Vec<u8>, Error> {
+    match format {
+        #[cfg(feature = "speedy")]
+        CacheFormat::Speedy => {
+            use speedy::Writable;
+            _result.write_to_vec().map_err(|e| Error::other(e.to_string()))
+        }
+        #[cfg(feature = "bincode")]
+        CacheFormat::Bincode => bincode::serialize(_result).map_err(|e| Error::other(e.to_string())),
+        #[cfg(feature = "json")]
+        CacheFormat::Json => serde_json::to_vec(_result).map_err(|e| Error::other(e.to_string())),
+    }
+}
+
+fn decode_result(_bytes: &[u8], format: CacheFormat) -> Result<ScandirResult, Error> {
+    match format {
+        #[cfg(feature = "speedy")]
+        CacheFormat::Speedy => {
+            use speedy::Readable;
+            ScandirResult::read_from_buffer(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+        }
+        #[cfg(feature = "bincode")]
+        CacheFormat::Bincode => bincode::deserialize(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string())),
+        #[cfg(feature = "json")]
+        CacheFormat::Json => serde_json::from_slice(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string())),
+    }
+}
+
+fn encode_error(_error: &(String, String), format: CacheFormat) -> Result<Vec<u8>, Error> {
+    match format {
+        #[cfg(feature = "speedy")]
+        CacheFormat::Speedy => {
+            use speedy::Writable;
+            _error.write_to_vec().map_err(|e| Error::other(e.to_string()))
+        }
+        #[cfg(feature = "bincode")]
+        CacheFormat::Bincode => bincode::serialize(_error).map_err(|e| Error::other(e.to_string())),
+        #[cfg(feature = "json")]
+        CacheFormat::Json => serde_json::to_vec(_error).map_err(|e| Error::other(e.to_string())),
+    }
+}
+
+fn decode_error(_bytes: &[u8], format: CacheFormat) -> Result<(String, String), Error> {
+    match format {
+        #[cfg(feature = "speedy")]
+        CacheFormat::Speedy => {
+            use speedy::Readable;
+            <(String, String)>::read_from_buffer(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+        }
+        #[cfg(feature = "bincode")]
+        CacheFormat::Bincode => bincode::deserialize(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string())),
+        #[cfg(feature = "json")]
+        CacheFormat::Json => serde_json::from_slice(_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string())),
+    }
+}
+
+fn write_framed(writer: &mut dyn Write, bytes: &[u8], crc: &mut Crc32) -> Result<(), Error> {
+    let len = u32::try_from(bytes.len()).map_err(|_| Error::new(ErrorKind::InvalidData, "entry too large to frame"))?;
+    if len == END_MARKER {
+        return Err(Error::new(ErrorKind::InvalidData, "entry too large to frame"));
+    }
+    let len_bytes = len.to_le_bytes();
+    writer.write_all(&len_bytes)?;
+    crc.update(&len_bytes);
+    writer.write_all(bytes)?;
+    crc.update(bytes);
+    Ok(())
+}
+
+fn read_framed(reader: &mut dyn Read, crc: &mut Crc32) -> Result<Option<Vec<u8>>, Error> {
+    let mut len_bytes = [0u8; 4];
+    reader
+        .read_exact(&mut len_bytes)
+        .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "truncated cache entry stream"))?;
+    let len = u32::from_le_bytes(len_bytes);
+    if len == END_MARKER {
+        return Ok(None);
+    }
+    crc.update(&len_bytes);
+    let mut bytes = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "truncated cache entry"))?;
+    crc.update(&bytes);
+    Ok(Some(bytes))
+}
+
+impl ScandirResults {
+    /// Snapshots this scan to `path` as a self-describing container: a
+    /// header recording `format`, the two entry streams (`results` then
+    /// `errors`), and a trailing entry-count/CRC-32 footer. Entries are
+    /// streamed straight to the (optionally compressed) writer one at a
+    /// time, so a multi-million-entry scan never needs a second copy of
+    /// itself in memory just to serialize it.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P, format: CacheFormat) -> Result<(), Error> {
+        let file = File::create(path)?;
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, format.tag()])?;
+
+        #[cfg(feature = "compress")]
+        let mut encoder = GzEncoder::new(out, Compression::default());
+        #[cfg(feature = "compress")]
+        let body: &mut dyn Write = &mut encoder;
+        #[cfg(not(feature = "compress"))]
+        let body: &mut dyn Write = &mut out;
+
+        let mut crc = Crc32::new();
+        for result in &self.results {
+            let bytes = encode_result(result, format)?;
+            write_framed(body, &bytes, &mut crc)?;
+        }
+        body.write_all(&END_MARKER.to_le_bytes())?;
+        for error in &self.errors {
+            let bytes = encode_error(error, format)?;
+            write_framed(body, &bytes, &mut crc)?;
+        }
+        body.write_all(&END_MARKER.to_le_bytes())?;
+        body.write_all(&(self.results.len() as u64).to_le_bytes())?;
+        body.write_all(&(self.errors.len() as u64).to_le_bytes())?;
+        body.write_all(&crc.finalize().to_le_bytes())?;
+        body.flush()?;
+
+        #[cfg(feature = "compress")]
+        {
+            let mut out = encoder.finish()?;
+            out.flush()?;
+        }
+        #[cfg(not(feature = "compress"))]
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reloads a file written by [`ScandirResults::save_to`]. A truncated
+    /// file, a format this build wasn't compiled with, or a checksum
+    /// mismatch is rejected with a descriptive [`Error`] rather than a
+    /// panic or silently wrong data.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; 6];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "truncated cache header"))?;
+        if header[0..4] != *MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a scandir cache file"));
+        }
+        if header[4] != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("cache file has version {}, expected {VERSION}", header[4]),
+            ));
+        }
+        let format = CacheFormat::from_tag(header[5])?;
+
+        #[cfg(feature = "compress")]
+        let mut body: Box<dyn Read> = Box::new(GzDecoder::new(reader));
+        #[cfg(not(feature = "compress"))]
+        let mut body: Box<dyn Read> = Box::new(reader);
+
+        let mut crc = Crc32::new();
+        let mut results = Vec::new();
+        while let Some(bytes) = read_framed(body.as_mut(), &mut crc)? {
+            results.push(decode_result(&bytes, format)?);
+        }
+        let mut errors = Vec::new();
+        while let Some(bytes) = read_framed(body.as_mut(), &mut crc)? {
+            errors.push(decode_error(&bytes, format)?);
+        }
+
+        let mut trailer = [0u8; 20];
+        body.read_exact(&mut trailer)
+            .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "truncated cache trailer"))?;
+        let result_count = u64::from_le_bytes(trailer[0..8].try_into().unwrap());
+        let error_count = u64::from_le_bytes(trailer[8..16].try_into().unwrap());
+        let stored_crc = u32::from_le_bytes(trailer[16..20].try_into().unwrap());
+        if result_count != results.len() as u64 || error_count != errors.len() as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "cache entry count mismatch"));
+        }
+        if stored_crc != crc.finalize() {
+            return Err(Error::new(ErrorKind::InvalidData, "cache checksum mismatch"));
+        }
+        // Drain (and discard) anything past our own trailer. With the
+        // `compress` feature this is what actually forces `GzDecoder` to
+        // validate the *outer* gzip footer (its own CRC/size check), which
+        // it otherwise never gets to run since we stop reading the moment
+        // our 20-byte trailer is in hand; without it, truncating or
+        // corrupting the tail of a compressed cache file silently goes
+        // undetected.
+        let mut trailing = Vec::new();
+        body.read_to_end(&mut trailing)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("cache file trailer corrupt: {e}")))?;
+        if !trailing.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "unexpected trailing data in cache file"));
+        }
+        Ok(ScandirResults { results, errors })
+    }
+}
+
+#[cfg(all(test, feature = "speedy"))]
+mod tests {
+    use super::*;
+    use crate::DirEntry;
+
+    fn sample() -> ScandirResults {
+        let mut results = ScandirResults::new();
+        results.results.push(ScandirResult::DirEntry(DirEntry {
+            path: "foo/bar".to_string(),
+            is_file: true,
+            st_size: 42,
+            ..Default::default()
+        }));
+        results.errors.push(("foo/baz".to_string(), "permission denied".to_string()));
+        results
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mactest-cache-test-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let results = sample();
+        results.save_to(&path, CacheFormat::Speedy).unwrap();
+        let loaded = ScandirResults::load_from(&path).unwrap();
+        assert_eq!(loaded, results);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_from_rejects_corrupted_checksum() {
+        let path = temp_path("corrupt");
+        sample().save_to(&path, CacheFormat::Speedy).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let err = ScandirResults::load_from(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn load_from_rejects_truncated_file() {
+        // Without the `compress` feature, truncating the framed body
+        // surfaces as `UnexpectedEof`; with it, the cut lands in the gzip
+        // footer, which `load_from` reports as `InvalidData`. Either way
+        // the truncation must be rejected, so assert on that rather than a
+        // specific `ErrorKind`.
+        let path = temp_path("truncated");
+        sample().save_to(&path, CacheFormat::Speedy).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 4);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(ScandirResults::load_from(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
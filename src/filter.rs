@@ -0,0 +1,243 @@
+use crate::Options;
+
+/// Folds ASCII letters to lowercase when `case_sensitive` is `false`,
+/// leaving everything else (including non-ASCII characters) untouched.
+/// `case_sensitive` is documented as controlling *ASCII* case folding, not
+/// full Unicode case folding (which `str::to_lowercase` does and which can
+/// change a string's length, e.g. Turkish `İ`).
+fn ascii_fold(s: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        s.to_string()
+    } else {
+        s.chars().map(|c| c.to_ascii_lowercase()).collect()
+    }
+}
+
+/// A single compiled glob pattern.
+///
+/// Patterns containing a `/` are anchored to the path relative to the scan
+/// root (gitignore semantics) and may use `**` to cross directory
+/// boundaries. Patterns without a `/` are matched against the entry's base
+/// name only, at any depth.
+#[derive(Debug, Clone)]
+struct GlobPattern {
+    anchored: bool,
+    segments: Vec<String>,
+}
+
+impl GlobPattern {
+    fn compile(pattern: &str, case_sensitive: bool) -> Self {
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        let fold = |s: &str| ascii_fold(s, case_sensitive);
+        let segments = if anchored {
+            pattern.split('/').map(fold).collect()
+        } else {
+            vec![fold(pattern)]
+        };
+        GlobPattern { anchored, segments }
+    }
+
+    fn matches(&self, name: &str, rel_path: &str, case_sensitive: bool) -> bool {
+        let fold = |s: &str| ascii_fold(s, case_sensitive);
+        if self.anchored {
+            let text: Vec<String> = rel_path.split('/').map(fold).collect();
+            let text_refs: Vec<&str> = text.iter().map(|s| s.as_str()).collect();
+            let pat_refs: Vec<&str> = self.segments.iter().map(|s| s.as_str()).collect();
+            glob_match_segments(&pat_refs, &text_refs)
+        } else {
+            wildcard_match(self.segments[0].as_bytes(), fold(name).as_bytes())
+        }
+    }
+}
+
+fn glob_match_segments(pattern: &[&str], text: &[&str]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(&"**") => {
+            glob_match_segments(&pattern[1..], text)
+                || (!text.is_empty() && glob_match_segments(pattern, &text[1..]))
+        }
+        Some(p) => {
+            if text.is_empty() {
+                false
+            } else {
+                wildcard_match(p.as_bytes(), text[0].as_bytes())
+                    && glob_match_segments(&pattern[1..], &text[1..])
+            }
+        }
+    }
+}
+
+/// Classic `*`/`?` wildcard matching of a single path segment.
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (m, n) = (pattern.len(), text.len());
+    let mut dp = vec![vec![false; n + 1]; m + 1];
+    dp[0][0] = true;
+    for (i, &p) in pattern.iter().enumerate() {
+        if p == b'*' {
+            dp[i + 1][0] = dp[i][0];
+        }
+    }
+    for i in 1..=m {
+        for j in 1..=n {
+            dp[i][j] = match pattern[i - 1] {
+                b'*' => dp[i - 1][j] || dp[i][j - 1],
+                b'?' => dp[i - 1][j - 1],
+                c => dp[i - 1][j - 1] && c == text[j - 1],
+            };
+        }
+    }
+    dp[m][n]
+}
+
+fn compile_list(patterns: &Option<Vec<String>>, case_sensitive: bool) -> Option<Vec<GlobPattern>> {
+    patterns
+        .as_ref()
+        .map(|patterns| patterns.iter().map(|p| GlobPattern::compile(p, case_sensitive)).collect())
+}
+
+fn any_matches(patterns: &Option<Vec<GlobPattern>>, name: &str, rel_path: &str, case_sensitive: bool) -> bool {
+    match patterns {
+        Some(patterns) => patterns.iter().any(|p| p.matches(name, rel_path, case_sensitive)),
+        None => false,
+    }
+}
+
+/// Precompiled `dir_include`/`dir_exclude`/`file_include`/`file_exclude`
+/// matchers, built once per scan so the hot walk loop only does matcher
+/// lookups instead of re-parsing glob strings for every entry.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    case_sensitive: bool,
+    dir_include: Option<Vec<GlobPattern>>,
+    dir_exclude: Option<Vec<GlobPattern>>,
+    file_include: Option<Vec<GlobPattern>>,
+    file_exclude: Option<Vec<GlobPattern>>,
+}
+
+impl Filter {
+    pub fn new(options: &Options) -> Self {
+        Filter {
+            case_sensitive: options.case_sensitive,
+            dir_include: compile_list(&options.dir_include, options.case_sensitive),
+            dir_exclude: compile_list(&options.dir_exclude, options.case_sensitive),
+            file_include: compile_list(&options.file_include, options.case_sensitive),
+            file_exclude: compile_list(&options.file_exclude, options.case_sensitive),
+        }
+    }
+
+    /// Whether a directory should be pruned (and never `readdir`'d) because
+    /// it matches `dir_exclude`.
+    pub fn is_dir_excluded(&self, name: &str, rel_path: &str) -> bool {
+        any_matches(&self.dir_exclude, name, rel_path, self.case_sensitive)
+    }
+
+    /// Whether a directory passes `dir_include` (always true if unset).
+    pub fn is_dir_included(&self, name: &str, rel_path: &str) -> bool {
+        match &self.dir_include {
+            Some(_) => any_matches(&self.dir_include, name, rel_path, self.case_sensitive),
+            None => true,
+        }
+    }
+
+    pub fn is_file_excluded(&self, name: &str, rel_path: &str) -> bool {
+        any_matches(&self.file_exclude, name, rel_path, self.case_sensitive)
+    }
+
+    pub fn is_file_included(&self, name: &str, rel_path: &str) -> bool {
+        match &self.file_include {
+            Some(_) => any_matches(&self.file_include, name, rel_path, self.case_sensitive),
+            None => true,
+        }
+    }
+
+    /// Whether the walk should descend into (`readdir`) this directory.
+    /// Only `dir_exclude` can prevent descent: a directory that merely
+    /// fails `dir_include` is still walked, since `dir_include` only gates
+    /// whether the directory itself is *emitted* (see
+    /// [`Filter::should_emit_dir`]), not whether its children are reached.
+    /// Pruning only on excludes means `dir_include: ["src"]` can still
+    /// find `project/sub/src`, rather than skipping `sub` (and everything
+    /// under it) for not being named `src`.
+    pub fn should_descend(&self, name: &str, rel_path: &str) -> bool {
+        !self.is_dir_excluded(name, rel_path)
+    }
+
+    /// Combines [`Filter::is_dir_excluded`] and [`Filter::is_dir_included`]:
+    /// `true` if the walk should emit this directory as a result. A
+    /// directory can still be descended into (see [`Filter::should_descend`])
+    /// even when this is `false`.
+    pub fn should_emit_dir(&self, name: &str, rel_path: &str) -> bool {
+        !self.is_dir_excluded(name, rel_path) && self.is_dir_included(name, rel_path)
+    }
+
+    /// Combines [`Filter::is_file_excluded`] and [`Filter::is_file_included`]:
+    /// `true` if the walk should emit this file.
+    pub fn should_emit_file(&self, name: &str, rel_path: &str) -> bool {
+        !self.is_file_excluded(name, rel_path) && self.is_file_included(name, rel_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Options, ReturnType};
+    use std::path::PathBuf;
+
+    fn filter(
+        dir_include: Option<Vec<&str>>,
+        dir_exclude: Option<Vec<&str>>,
+        file_include: Option<Vec<&str>>,
+        file_exclude: Option<Vec<&str>>,
+    ) -> Filter {
+        let to_strings = |v: Option<Vec<&str>>| v.map(|v| v.into_iter().map(String::from).collect());
+        Filter::new(&Options {
+            root_path: PathBuf::new(),
+            sorted: false,
+            skip_hidden: false,
+            max_depth: usize::MAX,
+            max_file_cnt: usize::MAX,
+            dir_include: to_strings(dir_include),
+            dir_exclude: to_strings(dir_exclude),
+            file_include: to_strings(file_include),
+            file_exclude: to_strings(file_exclude),
+            case_sensitive: true,
+            return_type: ReturnType::Base,
+            threads: 0,
+            follow_symlinks: false,
+        })
+    }
+
+    #[test]
+    fn double_star_crosses_directory_boundaries() {
+        let f = filter(None, Some(vec!["**/target"]), None, None);
+        assert!(f.is_dir_excluded("target", "target"));
+        assert!(f.is_dir_excluded("target", "a/b/target"));
+        assert!(!f.is_dir_excluded("targets", "a/targets"));
+    }
+
+    #[test]
+    fn anchored_pattern_matches_full_relative_path_only() {
+        let f = filter(None, None, None, Some(vec!["src/foo.rs"]));
+        assert!(f.is_file_excluded("foo.rs", "src/foo.rs"));
+        assert!(!f.is_file_excluded("foo.rs", "lib/src/foo.rs"));
+    }
+
+    #[test]
+    fn unanchored_pattern_matches_basename_at_any_depth() {
+        let f = filter(None, None, None, Some(vec!["*.log"]));
+        assert!(f.is_file_excluded("c.log", "c.log"));
+        assert!(f.is_file_excluded("c.log", "a/b/c.log"));
+        assert!(!f.is_file_excluded("c.logs", "a/b/c.logs"));
+    }
+
+    #[test]
+    fn dir_exclude_prunes_descent_while_include_only_gates_emission() {
+        let f = filter(Some(vec!["src"]), Some(vec!["node_modules"]), None, None);
+        assert!(!f.should_descend("node_modules", "node_modules"));
+        assert!(f.should_descend("sub", "sub"));
+        assert!(!f.is_dir_included("sub", "sub"));
+        assert!(f.is_dir_included("src", "project/sub/src"));
+    }
+}
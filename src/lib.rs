@@ -3,19 +3,36 @@ use std::io::{Error, ErrorKind};
 use std::time::SystemTime;
 use std::{
     path::{Path, PathBuf},
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread,
 };
 
 use flume::Receiver;
+#[cfg(feature = "speedy")]
 use speedy::{Readable, Writable};
 
 #[cfg(unix)]
 use expanduser::expanduser;
 
+#[cfg(any(feature = "bincode", feature = "json"))]
 #[macro_use]
 extern crate serde_derive;
 
+mod cache;
+mod filter;
+mod mode;
+mod search;
+mod time;
+mod walk;
+
+pub use cache::CacheFormat;
+pub use filter::Filter;
+pub use mode::{FileTypeKind, Permissions, Triplet};
+pub use search::{MatchContent, Search, SearchMatch, SearchResult, SearchResults};
+
 pub type ErrorsType = Vec<(String, String)>; // Tuple with file path and error message
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -37,6 +54,13 @@ pub struct Options {
     pub file_exclude: Option<Vec<String>>,
     pub case_sensitive: bool,
     pub return_type: ReturnType,
+    /// Number of worker threads used for the parallel directory walk.
+    /// `0` means "auto", i.e. one worker per available CPU.
+    pub threads: usize,
+    /// Descend into symlinked directories. Off by default, since a symlink
+    /// can point back into an ancestor and loop forever; when enabled, the
+    /// walk tracks `(st_dev, st_ino)` of the descent path to break cycles.
+    pub follow_symlinks: bool,
 }
 
 #[cfg_attr(feature = "speedy", derive(Readable, Writable))]
@@ -127,6 +151,12 @@ impl ScandirResults {
     }
 }
 
+impl Default for ScandirResults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn check_and_expand_path<P: AsRef<Path>>(path_str: P) -> Result<PathBuf, Error> {
     #[cfg(unix)]
     let path_result = fs::canonicalize(expanduser(path_str.as_ref().to_str().unwrap()).unwrap());
@@ -143,7 +173,7 @@ pub fn check_and_expand_path<P: AsRef<Path>>(path_str: P) -> Result<PathBuf, Err
             p
         }
         Err(e) => {
-            return Err(Error::new(ErrorKind::Other, e.to_string()));
+            return Err(Error::other(e.to_string()));
         }
     };
     Ok(path)
@@ -166,8 +196,8 @@ pub struct Scandir {
 
 impl Scandir {
     pub fn new<P: AsRef<Path>>(root_path: P, store: Option<bool>) -> Result<Self, Error> {
-        Ok(Scandir {
-            options: Options {
+        Ok(Scandir::with_options(
+            Options {
                 root_path: check_and_expand_path(root_path)?,
                 sorted: false,
                 skip_hidden: false,
@@ -179,7 +209,21 @@ impl Scandir {
                 file_exclude: None,
                 case_sensitive: false,
                 return_type: ReturnType::Base,
+                threads: 0,
+                follow_symlinks: false,
             },
+            store,
+        ))
+    }
+
+    /// Like [`Scandir::new`], but takes a fully assembled [`Options`]
+    /// instead of the all-defaults set `new` builds, e.g. to set
+    /// `dir_include`/`dir_exclude`, `follow_symlinks`, `threads`, or
+    /// `return_type: Ext`. `options.root_path` must already be resolved,
+    /// e.g. via [`check_and_expand_path`].
+    pub fn with_options(options: Options, store: Option<bool>) -> Self {
+        Scandir {
+            options,
             store: store.unwrap_or(true),
             entries: ScandirResults::new(),
             duration: Arc::new(Mutex::new(0.0)),
@@ -187,7 +231,7 @@ impl Scandir {
             thr: None,
             stop: Arc::new(AtomicBool::new(false)),
             rx: None,
-        })
+        }
     }
 
     pub fn duration(&mut self) -> f64 {
@@ -195,10 +239,60 @@ impl Scandir {
     }
 
     pub fn finished(&mut self) -> bool {
-        *self.duration.lock().unwrap() > 0.0
+        self.finished.load(Ordering::Relaxed)
     }
 
     pub fn finished2(&mut self) -> bool {
-        *self.duration.lock().unwrap() != 0.0
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Spawns the supervisor thread, which in turn fans work out across a
+    /// pool of work-stealing directory walkers (see [`walk::run`]).
+    pub fn start(&mut self) -> Result<(), Error> {
+        if self.thr.is_some() {
+            return Err(Error::other("Scandir already running"));
+        }
+        let (tx, rx) = flume::unbounded();
+        self.rx = Some(rx);
+        let root_path = self.options.root_path.clone();
+        let options = self.options.clone();
+        let filter = Filter::new(&self.options);
+        let stop = self.stop.clone();
+        let finished = self.finished.clone();
+        let duration = self.duration.clone();
+        self.thr = Some(thread::spawn(move || {
+            let start = std::time::Instant::now();
+            walk::run(root_path, options, filter, stop, tx);
+            *duration.lock().unwrap() = start.elapsed().as_secs_f64();
+            finished.store(true, Ordering::Relaxed);
+        }));
+        Ok(())
+    }
+
+    /// Blocks until the walk finishes and returns everything collected so
+    /// far. When `sorted` is set, the joined results are sorted by path so
+    /// output order no longer depends on worker thread scheduling.
+    pub fn collect(&mut self) -> ScandirResults {
+        if let Some(rx) = self.rx.take() {
+            for result in rx.iter() {
+                if self.store {
+                    match result {
+                        ScandirResult::Error(e) => self.entries.errors.push(e),
+                        result => self.entries.results.push(result),
+                    }
+                }
+            }
+        }
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+        if self.options.sorted {
+            walk::sort_results(&mut self.entries.results);
+        }
+        self.entries.clone()
     }
 }
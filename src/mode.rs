@@ -0,0 +1,167 @@
+use crate::DirEntryExt;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+
+/// File type decoded from the `S_IFMT` bits of `st_mode`, the way std's unix
+/// `MetadataExt`/`FileTypeExt` do it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileTypeKind {
+    RegularFile,
+    Directory,
+    Symlink,
+    Socket,
+    Fifo,
+    CharDevice,
+    BlockDevice,
+    /// `st_mode` didn't decode to a known type (e.g. it's synthetic on a
+    /// platform without real POSIX mode bits); falls back to the entry's
+    /// own `is_dir`/`is_file`/`is_symlink` flags, or this if none apply.
+    Unknown,
+}
+
+/// Owner/group/other read-write-execute triplet decoded from `st_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Triplet {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Triplet {
+    fn from_bits(bits: u32) -> Self {
+        Triplet {
+            read: bits & 0b100 != 0,
+            write: bits & 0b010 != 0,
+            execute: bits & 0b001 != 0,
+        }
+    }
+}
+
+/// Decoded `st_mode` permission bits: the owner/group/other triplets plus
+/// the setuid/setgid/sticky bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Permissions {
+    pub owner: Triplet,
+    pub group: Triplet,
+    pub other: Triplet,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub sticky: bool,
+}
+
+impl Permissions {
+    fn from_mode(mode: u32) -> Self {
+        Permissions {
+            owner: Triplet::from_bits((mode >> 6) & 0o7),
+            group: Triplet::from_bits((mode >> 3) & 0o7),
+            other: Triplet::from_bits(mode & 0o7),
+            setuid: mode & S_ISUID != 0,
+            setgid: mode & S_ISGID != 0,
+            sticky: mode & S_ISVTX != 0,
+        }
+    }
+}
+
+impl DirEntryExt {
+    /// Decodes the `S_IFMT` bits of `st_mode` into a [`FileTypeKind`]. Falls
+    /// back to the `is_dir`/`is_file`/`is_symlink` flags when `st_mode`
+    /// doesn't carry a recognized type, which is always the case on
+    /// platforms where it's synthetic (e.g. Windows).
+    pub fn file_type_kind(&self) -> FileTypeKind {
+        match self.st_mode & S_IFMT {
+            S_IFREG => FileTypeKind::RegularFile,
+            S_IFDIR => FileTypeKind::Directory,
+            S_IFLNK => FileTypeKind::Symlink,
+            S_IFSOCK => FileTypeKind::Socket,
+            S_IFIFO => FileTypeKind::Fifo,
+            S_IFCHR => FileTypeKind::CharDevice,
+            S_IFBLK => FileTypeKind::BlockDevice,
+            _ if self.is_symlink => FileTypeKind::Symlink,
+            _ if self.is_dir => FileTypeKind::Directory,
+            _ if self.is_file => FileTypeKind::RegularFile,
+            _ => FileTypeKind::Unknown,
+        }
+    }
+
+    /// Decodes the owner/group/other permission triplets plus the
+    /// setuid/setgid/sticky bits of `st_mode`.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_mode(self.st_mode)
+    }
+
+    /// Renders the classic `ls -l` style string, e.g. `-rwxr-xr-x`.
+    pub fn mode_string(&self) -> String {
+        let perms = self.permissions();
+        let mut s = String::with_capacity(10);
+        s.push(match self.file_type_kind() {
+            FileTypeKind::RegularFile => '-',
+            FileTypeKind::Directory => 'd',
+            FileTypeKind::Symlink => 'l',
+            FileTypeKind::Socket => 's',
+            FileTypeKind::Fifo => 'p',
+            FileTypeKind::CharDevice => 'c',
+            FileTypeKind::BlockDevice => 'b',
+            FileTypeKind::Unknown => '?',
+        });
+        push_triplet(&mut s, perms.owner, perms.setuid, b's', b'S');
+        push_triplet(&mut s, perms.group, perms.setgid, b's', b'S');
+        push_triplet(&mut s, perms.other, perms.sticky, b't', b'T');
+        s
+    }
+}
+
+fn push_triplet(s: &mut String, triplet: Triplet, special: bool, special_exec: u8, special_noexec: u8) {
+    s.push(if triplet.read { 'r' } else { '-' });
+    s.push(if triplet.write { 'w' } else { '-' });
+    s.push(match (triplet.execute, special) {
+        (true, true) => special_exec as char,
+        (false, true) => special_noexec as char,
+        (true, false) => 'x',
+        (false, false) => '-',
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_with_mode(mode: u32) -> DirEntryExt {
+        DirEntryExt { st_mode: mode, ..Default::default() }
+    }
+
+    #[test]
+    fn file_type_kind_decodes_s_ifmt_bits() {
+        assert_eq!(entry_with_mode(S_IFREG | 0o644).file_type_kind(), FileTypeKind::RegularFile);
+        assert_eq!(entry_with_mode(S_IFDIR | 0o755).file_type_kind(), FileTypeKind::Directory);
+        assert_eq!(entry_with_mode(S_IFLNK | 0o777).file_type_kind(), FileTypeKind::Symlink);
+    }
+
+    #[test]
+    fn file_type_kind_falls_back_to_flags_when_mode_is_synthetic() {
+        let entry = DirEntryExt { st_mode: 0, is_dir: true, ..Default::default() };
+        assert_eq!(entry.file_type_kind(), FileTypeKind::Directory);
+    }
+
+    #[test]
+    fn mode_string_renders_ls_style() {
+        assert_eq!(entry_with_mode(S_IFREG | 0o755).mode_string(), "-rwxr-xr-x");
+        assert_eq!(entry_with_mode(S_IFDIR | 0o750).mode_string(), "drwxr-x---");
+    }
+
+    #[test]
+    fn mode_string_renders_setuid_setgid_sticky() {
+        assert_eq!(entry_with_mode(S_IFREG | S_ISUID | 0o755).mode_string(), "-rwsr-xr-x");
+        assert_eq!(entry_with_mode(S_IFDIR | S_ISVTX | 0o755).mode_string(), "drwxr-xr-t");
+    }
+}
@@ -0,0 +1,305 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use flume::{unbounded, Receiver};
+use regex::bytes::Regex;
+#[cfg(feature = "speedy")]
+use speedy::{Readable, Writable};
+
+use crate::{check_and_expand_path, ErrorsType, Filter, Options, ReturnType, ScandirResult};
+
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchContent {
+    /// The matched bytes were valid UTF-8.
+    Text(String),
+    /// The matched bytes were not valid UTF-8 (binary file).
+    Binary(Vec<u8>),
+}
+
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub path: String,
+    /// 1-based line number the match starts on.
+    pub line: u64,
+    /// Byte offset of the match from the start of the file.
+    pub offset: u64,
+    pub content: MatchContent,
+}
+
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchResult {
+    Match(SearchMatch),
+    Error((String, String)),
+}
+
+#[cfg_attr(feature = "speedy", derive(Readable, Writable))]
+#[cfg_attr(
+    any(feature = "bincode", feature = "json"),
+    derive(Deserialize, Serialize)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    pub errors: ErrorsType,
+}
+
+impl SearchResults {
+    pub fn new() -> Self {
+        SearchResults {
+            results: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl Default for SearchResults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Streams regex matches against file *contents*, reusing [`crate::walk::run`]
+/// (the same work-stealing walker [`crate::Scandir`] drives) to pick
+/// candidate files, so `dir_include`/`dir_exclude`/`file_include`/
+/// `file_exclude`, `follow_symlinks`, `threads`, and `max_file_cnt` all
+/// behave identically between the two. A supervisor thread drains the
+/// walker's entries, runs the regex over every file it emits, and pushes
+/// [`SearchResult`]s over its own `flume` channel while `duration`/
+/// `finished`/`stop` let the caller supervise it.
+#[derive(Debug)]
+pub struct Search {
+    root_path: PathBuf,
+    options: Options,
+    pattern: Regex,
+    store: bool,
+    entries: SearchResults,
+    duration: Arc<Mutex<f64>>,
+    finished: Arc<AtomicBool>,
+    thr: Option<thread::JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    rx: Option<Receiver<SearchResult>>,
+}
+
+impl Search {
+    pub fn new<P: AsRef<Path>>(root_path: P, pattern: &str, store: Option<bool>) -> Result<Self, Error> {
+        let root_path = check_and_expand_path(root_path)?;
+        let options = Options {
+            root_path: root_path.clone(),
+            sorted: false,
+            skip_hidden: false,
+            max_depth: usize::MAX,
+            max_file_cnt: usize::MAX,
+            dir_include: None,
+            dir_exclude: None,
+            file_include: None,
+            file_exclude: None,
+            case_sensitive: false,
+            return_type: ReturnType::Base,
+            threads: 0,
+            follow_symlinks: false,
+        };
+        Search::with_options(options, pattern, store)
+    }
+
+    /// Like [`Search::new`], but takes a fully assembled [`Options`]
+    /// instead of the all-defaults set `new` builds, e.g. to set
+    /// `dir_include`/`file_exclude` so the regex only runs over a subset
+    /// of the tree. `options.root_path` must already be resolved, e.g. via
+    /// [`check_and_expand_path`].
+    pub fn with_options(options: Options, pattern: &str, store: Option<bool>) -> Result<Self, Error> {
+        let pattern = Regex::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+        Ok(Search {
+            root_path: options.root_path.clone(),
+            options,
+            pattern,
+            store: store.unwrap_or(true),
+            entries: SearchResults::new(),
+            duration: Arc::new(Mutex::new(0.0)),
+            finished: Arc::new(AtomicBool::new(false)),
+            thr: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            rx: None,
+        })
+    }
+
+    pub fn duration(&mut self) -> f64 {
+        *self.duration.lock().unwrap()
+    }
+
+    pub fn finished(&mut self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn start(&mut self) -> Result<(), Error> {
+        if self.thr.is_some() {
+            return Err(Error::other("Search already running"));
+        }
+        let (tx, rx) = unbounded();
+        self.rx = Some(rx);
+        let root_path = self.root_path.clone();
+        let options = self.options.clone();
+        let pattern = self.pattern.clone();
+        let stop = self.stop.clone();
+        let finished = self.finished.clone();
+        let duration = self.duration.clone();
+        self.thr = Some(thread::spawn(move || {
+            let start = std::time::Instant::now();
+            let filter = Filter::new(&options);
+            walk(&root_path, &options, &filter, &pattern, &stop, &tx);
+            *duration.lock().unwrap() = start.elapsed().as_secs_f64();
+            finished.store(true, Ordering::Relaxed);
+        }));
+        Ok(())
+    }
+
+    /// Blocks until the background walk finishes and returns everything
+    /// collected so far (or, if `store` is `false`, drains and discards the
+    /// channel while still returning an empty [`SearchResults`]).
+    pub fn collect(&mut self) -> SearchResults {
+        if let Some(rx) = self.rx.take() {
+            for result in rx.iter() {
+                if self.store {
+                    match result {
+                        SearchResult::Error(e) => self.entries.errors.push(e),
+                        result => self.entries.results.push(result),
+                    }
+                }
+            }
+        }
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+        self.entries.clone()
+    }
+}
+
+/// Drives [`crate::walk::run`] to enumerate candidate files, then runs
+/// `pattern` over every file it emits. Reusing the shared walker (instead of
+/// a hand-rolled recursive `readdir`) means `follow_symlinks`, `threads`,
+/// and `max_file_cnt` are honored here exactly the same way `Scandir` honors
+/// them, not just the include/exclude filters.
+fn walk(root_path: &Path, options: &Options, filter: &Filter, pattern: &Regex, stop: &Arc<AtomicBool>, tx: &flume::Sender<SearchResult>) {
+    let (walk_tx, walk_rx) = unbounded();
+    crate::walk::run(root_path.to_path_buf(), options.clone(), filter.clone(), stop.clone(), walk_tx);
+    for result in walk_rx.iter() {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        match result {
+            ScandirResult::DirEntry(entry) if entry.is_file => {
+                search_file(Path::new(&entry.path), pattern, stop, tx);
+            }
+            ScandirResult::DirEntryExt(entry) if entry.is_file => {
+                search_file(Path::new(&entry.path), pattern, stop, tx);
+            }
+            ScandirResult::DirEntry(_) | ScandirResult::DirEntryExt(_) => {}
+            ScandirResult::Error(e) => {
+                let _ = tx.send(SearchResult::Error(e));
+            }
+        }
+    }
+}
+
+fn search_file(path: &Path, pattern: &Regex, stop: &AtomicBool, tx: &flume::Sender<SearchResult>) {
+    let mut content = Vec::new();
+    let mut file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = tx.send(SearchResult::Error((path.to_string_lossy().to_string(), e.to_string())));
+            return;
+        }
+    };
+    if let Err(e) = file.read_to_end(&mut content) {
+        let _ = tx.send(SearchResult::Error((path.to_string_lossy().to_string(), e.to_string())));
+        return;
+    }
+    for m in pattern.find_iter(&content) {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let line = content[..m.start()].iter().filter(|&&b| b == b'\n').count() as u64 + 1;
+        let matched = m.as_bytes();
+        let search_match = SearchMatch {
+            path: path.to_string_lossy().to_string(),
+            line,
+            offset: m.start() as u64,
+            content: match std::str::from_utf8(matched) {
+                Ok(s) => MatchContent::Text(s.to_string()),
+                Err(_) => MatchContent::Binary(matched.to_vec()),
+            },
+        };
+        let _ = tx.send(SearchResult::Match(search_match));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mactest-search-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run_search(dir: &Path, pattern: &str) -> Vec<SearchMatch> {
+        let mut search = Search::new(dir, pattern, Some(true)).unwrap();
+        search.start().unwrap();
+        search
+            .collect()
+            .results
+            .into_iter()
+            .map(|r| match r {
+                SearchResult::Match(m) => m,
+                SearchResult::Error(e) => panic!("unexpected search error: {e:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_text_content_in_a_real_file() {
+        let dir = temp_dir("text-match");
+        fs::write(dir.join("hello.txt"), b"line one\nhello world\nline three").unwrap();
+        let matches = run_search(&dir, "hello");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].content, MatchContent::Text("hello".to_string()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn matches_binary_content_when_not_valid_utf8() {
+        let dir = temp_dir("binary-match");
+        let mut content = vec![b'a', b'b', 0xff, 0xfe, b'c', b'd'];
+        content.extend_from_slice(b"trailer");
+        fs::write(dir.join("blob.bin"), &content).unwrap();
+        let matches = run_search(&dir, "(?-u)ab\\xff\\xfecd");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].content, MatchContent::Binary(vec![b'a', b'b', 0xff, 0xfe, b'c', b'd']));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::DirEntryExt;
+
+impl DirEntryExt {
+    /// `st_ctime`/`st_ctime_nsec` as a split `(secs, nsec)` pair, for callers
+    /// that need to round-trip the exact on-disk timestamp through the
+    /// `speedy`/`bincode`/`json` serializers. `0` nanoseconds on platforms
+    /// that don't report sub-second precision.
+    pub fn st_ctime_parts(&self) -> (i64, i64) {
+        split(self.st_ctime)
+    }
+
+    /// `st_mtime`/`st_mtime_nsec` as a split `(secs, nsec)` pair.
+    pub fn st_mtime_parts(&self) -> (i64, i64) {
+        split(self.st_mtime)
+    }
+
+    /// `st_atime`/`st_atime_nsec` as a split `(secs, nsec)` pair.
+    pub fn st_atime_parts(&self) -> (i64, i64) {
+        split(self.st_atime)
+    }
+}
+
+fn split(time: Option<SystemTime>) -> (i64, i64) {
+    let Some(time) = time else {
+        return (0, 0);
+    };
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos() as i64),
+        Err(e) => {
+            // Before the epoch: round the seconds towards negative infinity
+            // and keep nsec non-negative, mirroring timespec semantics.
+            let d = e.duration();
+            if d.subsec_nanos() == 0 {
+                (-(d.as_secs() as i64), 0)
+            } else {
+                (-(d.as_secs() as i64) - 1, 1_000_000_000 - d.subsec_nanos() as i64)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn split_handles_none() {
+        assert_eq!(split(None), (0, 0));
+    }
+
+    #[test]
+    fn split_handles_epoch() {
+        assert_eq!(split(Some(UNIX_EPOCH)), (0, 0));
+    }
+
+    #[test]
+    fn split_handles_post_epoch_with_nanos() {
+        let t = UNIX_EPOCH + Duration::new(100, 250);
+        assert_eq!(split(Some(t)), (100, 250));
+    }
+
+    #[test]
+    fn split_handles_pre_epoch_exact_seconds() {
+        let t = UNIX_EPOCH - Duration::new(5, 0);
+        assert_eq!(split(Some(t)), (-5, 0));
+    }
+
+    #[test]
+    fn split_handles_pre_epoch_with_nanos() {
+        let t = UNIX_EPOCH - Duration::new(5, 0) + Duration::new(0, 100_000_000);
+        assert_eq!(split(Some(t)), (-5, 100_000_000));
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use flume::Sender;
+
+use crate::{DirEntry, DirEntryExt, Filter, Options, ReturnType, ScandirResult};
+
+/// Identifies a directory for symlink/hardlink cycle detection. Prefers the
+/// `(st_dev, st_ino)` pair; falls back to a canonicalized path on platforms
+/// where inode numbers aren't available.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CycleKey {
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+#[cfg(unix)]
+fn cycle_key(path: &Path) -> CycleKey {
+    use std::os::unix::fs::MetadataExt;
+    match fs::metadata(path) {
+        Ok(meta) => CycleKey::Inode(meta.dev(), meta.ino()),
+        Err(_) => CycleKey::Path(path.to_path_buf()),
+    }
+}
+
+#[cfg(not(unix))]
+fn cycle_key(path: &Path) -> CycleKey {
+    CycleKey::Path(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
+struct Job {
+    path: PathBuf,
+    rel: String,
+    depth: usize,
+    /// (dev, ino) pairs of every directory on the path from the scan root
+    /// down to (and including) this job's directory.
+    ancestors: Arc<Vec<CycleKey>>,
+}
+
+/// Shared work queue of directories still to be `readdir`'d. `pending`
+/// tracks directories that have been pushed but not yet fully processed, so
+/// idle workers can tell "queue momentarily empty" apart from "walk done".
+struct WorkQueue {
+    jobs: Mutex<VecDeque<Job>>,
+    pending: AtomicUsize,
+}
+
+impl WorkQueue {
+    fn push(&self, job: Job) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.jobs.lock().unwrap().push_back(job);
+    }
+
+    fn pop(&self) -> Option<Job> {
+        self.jobs.lock().unwrap().pop_front()
+    }
+
+    fn done(&self) -> bool {
+        self.pending.load(Ordering::SeqCst) == 0
+    }
+
+    fn finish_one(&self) {
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn resolve_thread_count(threads: usize) -> usize {
+    if threads == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        threads
+    }
+}
+
+/// Walks `root_path` with a pool of work-stealing worker threads, pushing
+/// every entry (and error) over `tx`. Blocks until every worker has run out
+/// of work; the caller is expected to call this from its own background
+/// thread, the same way the single-threaded walk used to run.
+pub(crate) fn run(root_path: PathBuf, options: Options, filter: Filter, stop: Arc<AtomicBool>, tx: Sender<ScandirResult>) {
+    let num_threads = resolve_thread_count(options.threads).max(1);
+    let queue = Arc::new(WorkQueue {
+        jobs: Mutex::new(VecDeque::new()),
+        pending: AtomicUsize::new(0),
+    });
+    let file_cnt = Arc::new(AtomicUsize::new(0));
+    let root_key = cycle_key(&root_path);
+    queue.push(Job {
+        path: root_path,
+        rel: String::new(),
+        depth: 0,
+        ancestors: Arc::new(vec![root_key]),
+    });
+
+    let handles: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let queue = queue.clone();
+            let options = options.clone();
+            let filter = filter.clone();
+            let stop = stop.clone();
+            let tx = tx.clone();
+            let file_cnt = file_cnt.clone();
+            thread::spawn(move || worker(queue, options, filter, stop, tx, file_cnt))
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
+
+fn worker(
+    queue: Arc<WorkQueue>,
+    options: Options,
+    filter: Filter,
+    stop: Arc<AtomicBool>,
+    tx: Sender<ScandirResult>,
+    file_cnt: Arc<AtomicUsize>,
+) {
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let job = match queue.pop() {
+            Some(job) => job,
+            None => {
+                if queue.done() {
+                    return;
+                }
+                thread::yield_now();
+                continue;
+            }
+        };
+        process_dir(&job, &queue, &options, &filter, &stop, &tx, &file_cnt);
+        queue.finish_one();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_dir(
+    job: &Job,
+    queue: &Arc<WorkQueue>,
+    options: &Options,
+    filter: &Filter,
+    stop: &AtomicBool,
+    tx: &Sender<ScandirResult>,
+    file_cnt: &AtomicUsize,
+) {
+    let read_dir = match fs::read_dir(&job.path) {
+        Ok(rd) => rd,
+        Err(e) => {
+            let _ = tx.send(ScandirResult::Error((job.path.to_string_lossy().to_string(), e.to_string())));
+            return;
+        }
+    };
+    for entry in read_dir {
+        if stop.load(Ordering::Relaxed) || file_cnt.load(Ordering::Relaxed) >= options.max_file_cnt {
+            return;
+        }
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if options.skip_hidden && name.starts_with('.') {
+            continue;
+        }
+        let child_rel = if job.rel.is_empty() { name.clone() } else { format!("{}/{}", job.rel, name) };
+        let file_type = match entry.file_type() {
+            Ok(ft) => ft,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+        let is_symlink = file_type.is_symlink();
+        // `file_type` never follows symlinks, so a symlink always reports
+        // `is_dir() == false`. Stat through it to learn what it actually
+        // points at so dir/file filters still apply to it; `follow` then
+        // separately decides whether we *descend* into a symlinked dir.
+        let target_is_dir = if is_symlink {
+            fs::metadata(&path).map(|m| m.is_dir()).unwrap_or(false)
+        } else {
+            file_type.is_dir()
+        };
+        let follow = is_symlink && options.follow_symlinks && target_is_dir;
+        if target_is_dir {
+            if !filter.should_descend(&name, &child_rel) {
+                continue;
+            }
+            let included = filter.is_dir_included(&name, &child_rel);
+            if job.depth < options.max_depth && (follow || !is_symlink) {
+                if is_symlink {
+                    let key = cycle_key(&path);
+                    if job.ancestors.contains(&key) {
+                        let _ = tx.send(ScandirResult::Error((path.to_string_lossy().to_string(), "symlink loop detected, not descending".to_string())));
+                        if file_cnt.fetch_add(1, Ordering::Relaxed) >= options.max_file_cnt {
+                            return;
+                        }
+                        let _ = tx.send(make_result(&entry, &options.return_type));
+                        continue;
+                    }
+                    let mut ancestors = (*job.ancestors).clone();
+                    ancestors.push(key);
+                    queue.push(Job {
+                        path,
+                        rel: child_rel,
+                        depth: job.depth + 1,
+                        ancestors: Arc::new(ancestors),
+                    });
+                } else {
+                    let mut ancestors = (*job.ancestors).clone();
+                    ancestors.push(cycle_key(&path));
+                    queue.push(Job {
+                        path,
+                        rel: child_rel,
+                        depth: job.depth + 1,
+                        ancestors: Arc::new(ancestors),
+                    });
+                }
+            }
+            if !included {
+                continue;
+            }
+        } else if !filter.should_emit_file(&name, &child_rel) {
+            continue;
+        }
+        if file_cnt.fetch_add(1, Ordering::Relaxed) >= options.max_file_cnt {
+            return;
+        }
+        let _ = tx.send(make_result(&entry, &options.return_type));
+    }
+}
+
+fn make_result(entry: &fs::DirEntry, return_type: &ReturnType) -> ScandirResult {
+    let path = entry.path();
+    let is_symlink = entry.file_type().map(|ft| ft.is_symlink()).unwrap_or(false);
+    let meta = match entry.metadata() {
+        Ok(m) => m,
+        Err(e) => return ScandirResult::Error((path.to_string_lossy().to_string(), e.to_string())),
+    };
+    match return_type {
+        ReturnType::Base => ScandirResult::DirEntry(to_dir_entry(&path, &meta, is_symlink)),
+        ReturnType::Ext => ScandirResult::DirEntryExt(to_dir_entry_ext(&path, &meta, is_symlink)),
+    }
+}
+
+fn to_dir_entry(path: &std::path::Path, meta: &fs::Metadata, is_symlink: bool) -> DirEntry {
+    DirEntry {
+        path: path.to_string_lossy().to_string(),
+        is_symlink,
+        is_dir: meta.is_dir(),
+        is_file: meta.is_file(),
+        st_ctime: meta.created().ok(),
+        st_mtime: meta.modified().ok(),
+        st_atime: meta.accessed().ok(),
+        st_size: meta.len(),
+    }
+}
+
+#[cfg(unix)]
+fn to_dir_entry_ext(path: &std::path::Path, meta: &fs::Metadata, is_symlink: bool) -> DirEntryExt {
+    use std::os::unix::fs::MetadataExt;
+    let timespec_to_time = |secs: i64, nsec: i64| {
+        if secs >= 0 {
+            SystemTime::UNIX_EPOCH + std::time::Duration::new(secs as u64, nsec as u32)
+        } else {
+            // `secs` is negative (pre-epoch) while `nsec` is still the
+            // non-negative sub-second part of the *same* timespec, so add
+            // it back on top rather than folding it into the subtraction.
+            (SystemTime::UNIX_EPOCH - std::time::Duration::new((-secs) as u64, 0)) + std::time::Duration::new(0, nsec as u32)
+        }
+    };
+    DirEntryExt {
+        path: path.to_string_lossy().to_string(),
+        is_symlink,
+        is_dir: meta.is_dir(),
+        is_file: meta.is_file(),
+        st_ctime: Some(timespec_to_time(meta.ctime(), meta.ctime_nsec())),
+        st_mtime: Some(timespec_to_time(meta.mtime(), meta.mtime_nsec())),
+        st_atime: Some(timespec_to_time(meta.atime(), meta.atime_nsec())),
+        st_size: meta.size(),
+        st_blksize: meta.blksize(),
+        st_blocks: meta.blocks(),
+        st_mode: meta.mode(),
+        st_nlink: meta.nlink(),
+        st_uid: meta.uid(),
+        st_gid: meta.gid(),
+        st_ino: meta.ino(),
+        st_dev: meta.dev(),
+        st_rdev: meta.rdev(),
+    }
+}
+
+#[cfg(not(unix))]
+fn to_dir_entry_ext(path: &std::path::Path, meta: &fs::Metadata, is_symlink: bool) -> DirEntryExt {
+    DirEntryExt {
+        path: path.to_string_lossy().to_string(),
+        is_symlink,
+        is_dir: meta.is_dir(),
+        is_file: meta.is_file(),
+        st_ctime: meta.created().ok(),
+        st_mtime: meta.modified().ok(),
+        st_atime: meta.accessed().ok(),
+        st_size: meta.len(),
+        ..Default::default()
+    }
+}
+
+/// Sorts a freshly joined result set by path so output order is stable
+/// regardless of which worker happened to win the race for a given entry.
+pub(crate) fn sort_results(results: &mut [ScandirResult]) {
+    results.sort_by(|a, b| result_path(a).cmp(result_path(b)));
+}
+
+fn result_path(result: &ScandirResult) -> &str {
+    match result {
+        ScandirResult::DirEntry(e) => &e.path,
+        ScandirResult::DirEntryExt(e) => &e.path,
+        ScandirResult::Error((path, _)) => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mactest-walk-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn base_options(root: &Path) -> Options {
+        Options {
+            root_path: root.to_path_buf(),
+            sorted: true,
+            skip_hidden: false,
+            max_depth: usize::MAX,
+            max_file_cnt: usize::MAX,
+            dir_include: None,
+            dir_exclude: None,
+            file_include: None,
+            file_exclude: None,
+            case_sensitive: true,
+            return_type: ReturnType::Base,
+            threads: 4,
+            follow_symlinks: false,
+        }
+    }
+
+    fn run_walk(options: Options) -> Vec<ScandirResult> {
+        let filter = Filter::new(&options);
+        let (tx, rx) = flume::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let root = options.root_path.clone();
+        run(root, options, filter, stop, tx);
+        let mut results: Vec<_> = rx.iter().collect();
+        sort_results(&mut results);
+        results
+    }
+
+    fn paths(results: &[ScandirResult]) -> Vec<String> {
+        results.iter().map(|r| result_path(r).to_string()).collect()
+    }
+
+    #[test]
+    fn resolve_thread_count_auto_detects_when_zero() {
+        assert!(resolve_thread_count(0) >= 1);
+        assert_eq!(resolve_thread_count(4), 4);
+    }
+
+    #[test]
+    fn sorted_output_is_stable_regardless_of_worker_race() {
+        let dir = temp_dir("sorted");
+        for name in ["c.txt", "a.txt", "b.txt"] {
+            fs::write(dir.join(name), b"x").unwrap();
+        }
+        let results = run_walk(base_options(&dir));
+        let mut expected: Vec<_> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|n| dir.join(n).to_string_lossy().to_string())
+            .collect();
+        expected.sort();
+        assert_eq!(paths(&results), expected);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_file_still_goes_through_file_exclude() {
+        use std::os::unix::fs::symlink;
+        let dir = temp_dir("symlink-file-exclude");
+        fs::write(dir.join("secret.bak"), b"x").unwrap();
+        symlink(dir.join("secret.bak"), dir.join("link.bak")).unwrap();
+        let mut options = base_options(&dir);
+        options.file_exclude = Some(vec!["*.bak".to_string()]);
+        let results = run_walk(options);
+        assert!(paths(&results).is_empty(), "symlinked file matching file_exclude must not be emitted, got {:?}", paths(&results));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_dir_still_goes_through_dir_exclude() {
+        use std::os::unix::fs::symlink;
+        let dir = temp_dir("symlink-dir-exclude");
+        fs::create_dir(dir.join("real_node_modules")).unwrap();
+        fs::write(dir.join("real_node_modules/pkg.json"), b"{}").unwrap();
+        // The symlink itself is named `node_modules`, the way a vendored
+        // dependency symlink normally would be.
+        symlink(dir.join("real_node_modules"), dir.join("node_modules")).unwrap();
+        let mut options = base_options(&dir);
+        options.dir_exclude = Some(vec!["node_modules".to_string()]);
+        let results = run_walk(options);
+        let found = paths(&results);
+        let excluded_path = dir.join("node_modules").to_string_lossy().to_string();
+        assert!(
+            !found.contains(&excluded_path),
+            "symlinked dir matching dir_exclude must not be emitted: {found:?}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loop_is_detected_and_does_not_hang() {
+        use std::os::unix::fs::symlink;
+        let dir = temp_dir("symlink-loop");
+        fs::create_dir(dir.join("a")).unwrap();
+        symlink(&dir, dir.join("a/loop")).unwrap();
+        let mut options = base_options(&dir);
+        options.follow_symlinks = true;
+        let results = run_walk(options);
+        assert!(results
+            .iter()
+            .any(|r| matches!(r, ScandirResult::Error((_, msg)) if msg.contains("symlink loop"))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}